@@ -4,7 +4,7 @@ use crate::{
     transaction::{DbTx, DbTxMut},
     DatabaseError,
 };
-use std::{fmt::Debug, sync::Arc};
+use std::{fmt::Debug, future::Future, sync::Arc, time::Duration};
 
 /// Main Database trait that can open read-only and read-write transactions.
 ///
@@ -50,6 +50,121 @@ pub trait Database: Send + Sync + Sealed {
 
         Ok(res)
     }
+
+    /// Takes a fallible function and passes a read-only transaction into it. As with [`view`](
+    /// Self::view), the transaction is read-only and closed without side effects, so there is
+    /// nothing to roll back on error; this exists for symmetry with
+    /// [`try_update`](Self::try_update) and to let callers use `?` inside the closure.
+    fn try_view<T, E, F>(&self, f: F) -> Result<T, E>
+    where
+        F: FnOnce(&Self::TX) -> Result<T, E>,
+        E: From<DatabaseError>,
+    {
+        let tx = self.tx()?;
+
+        let res = f(&tx)?;
+        tx.commit()?;
+
+        Ok(res)
+    }
+
+    /// Takes a fallible function and passes a write-read transaction into it. If the closure
+    /// returns `Ok`, the transaction is committed and the value returned. If it returns `Err`,
+    /// the transaction is rolled back (dropped without committing) and the error is propagated,
+    /// so a failed multi-step write leaves the database untouched.
+    fn try_update<T, E, F>(&self, f: F) -> Result<T, E>
+    where
+        F: FnOnce(&Self::TXMut) -> Result<T, E>,
+        E: From<DatabaseError>,
+    {
+        let tx = self.tx_mut()?;
+
+        match f(&tx) {
+            Ok(res) => {
+                tx.commit()?;
+                Ok(res)
+            }
+            Err(err) => {
+                drop(tx);
+                Err(err)
+            }
+        }
+    }
+
+    /// Resilient variant of [`update`](Self::update) for use under concurrent writers, where
+    /// `tx_mut().commit()` can fail transiently (a map-full resize, a write conflict, ...).
+    ///
+    /// On a commit error the `policy` classifies as retryable, reopens a fresh write transaction
+    /// and re-invokes `f`, up to `policy.max_attempts` attempts with `policy.backoff` between
+    /// them. `f` must be safely re-runnable, since it may execute more than once.
+    fn update_with_retry<T, F>(&self, policy: RetryPolicy, mut f: F) -> Result<T, DatabaseError>
+    where
+        F: FnMut(&Self::TXMut) -> T,
+    {
+        let mut attempt = 1;
+        loop {
+            let tx = self.tx_mut()?;
+            let res = f(&tx);
+
+            match tx.commit() {
+                Ok(_) => return Ok(res),
+                Err(err) if attempt < policy.max_attempts() && policy.is_retryable(&err) => {
+                    attempt += 1;
+                    if !policy.backoff().is_zero() {
+                        std::thread::sleep(policy.backoff());
+                    }
+                }
+                Err(err) => return Err(err),
+            }
+        }
+    }
+}
+
+/// Configures how [`Database::update_with_retry`] behaves when a write transaction's commit
+/// fails with a transient error.
+///
+/// Fields are private and only constructible through [`RetryPolicy::new`] so "at least one
+/// attempt" is an invariant callers can rely on, not just a suggestion.
+#[derive(Clone, Debug)]
+pub struct RetryPolicy {
+    max_attempts: usize,
+    backoff: Duration,
+    is_retryable: fn(&DatabaseError) -> bool,
+}
+
+impl RetryPolicy {
+    /// Creates a policy with the given attempt count and backoff. No errors are considered
+    /// retryable until [`RetryPolicy::retry_if`] opts specific variants in.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max_attempts` is `0`: "maximum number of attempts, including the first one"
+    /// cannot be zero, since [`Database::update_with_retry`] must make at least one attempt.
+    pub fn new(max_attempts: usize, backoff: Duration) -> Self {
+        assert!(max_attempts >= 1, "RetryPolicy requires at least one attempt");
+        Self { max_attempts, backoff, is_retryable: |_| false }
+    }
+
+    /// Sets the predicate used to decide whether a failed commit should be retried.
+    pub fn retry_if(mut self, is_retryable: fn(&DatabaseError) -> bool) -> Self {
+        self.is_retryable = is_retryable;
+        self
+    }
+
+    /// Maximum number of attempts, including the first one.
+    pub fn max_attempts(&self) -> usize {
+        self.max_attempts
+    }
+
+    /// Delay to wait between attempts.
+    pub fn backoff(&self) -> Duration {
+        self.backoff
+    }
+
+    /// Returns whether `err` is classified as retryable by this policy.
+    pub fn is_retryable(&self, err: &DatabaseError) -> bool {
+        (self.is_retryable)(err)
+    }
 }
 
 // 原子引用计数
@@ -84,3 +199,390 @@ impl<DB: Database> Database for &DB {
         <DB as Database>::tx_mut(self)
     }
 }
+
+/// Async mirror of [`Database`], for backends whose transactions are inherently I/O-bound (a
+/// remote RPC-served state provider, an S3-backed cold store, a pooled connection store, ...).
+///
+/// `tx`/`tx_mut` return futures instead of blocking, and `view`/`update` take closures that
+/// return a future rather than a plain value. Sealed trait which cannot be implemented by 3rd
+/// parties, exposed only for consumption.
+pub trait AsyncDatabase: Send + Sync + Sealed {
+    /// Read-Only database transaction
+    type TX: DbTx + Send + Sync + Debug + 'static;
+    /// Read-Write database transaction
+    type TXMut: DbTxMut + DbTx + TableImporter + Send + Sync + Debug + 'static;
+
+    /// Create read only transaction.
+    fn tx(&self) -> impl Future<Output = Result<Self::TX, DatabaseError>> + Send;
+
+    /// Create read write transaction only possible if database is open with write access.
+    fn tx_mut(&self) -> impl Future<Output = Result<Self::TXMut, DatabaseError>> + Send;
+
+    /// Takes a function and passes a read-only transaction into it, making sure it's closed in
+    /// the end of the execution.
+    fn view<T, F, Fut>(&self, f: F) -> impl Future<Output = Result<T, DatabaseError>> + Send
+    where
+        F: FnOnce(&Self::TX) -> Fut + Send,
+        Fut: Future<Output = T> + Send,
+    {
+        async move {
+            let tx = self.tx().await?;
+
+            let res = f(&tx).await;
+            tx.commit()?;
+
+            Ok(res)
+        }
+    }
+
+    /// Takes a function and passes a write-read transaction into it, making sure it's committed
+    /// in the end of the execution.
+    fn update<T, F, Fut>(&self, f: F) -> impl Future<Output = Result<T, DatabaseError>> + Send
+    where
+        F: FnOnce(&Self::TXMut) -> Fut + Send,
+        Fut: Future<Output = T> + Send,
+    {
+        async move {
+            let tx = self.tx_mut().await?;
+
+            let res = f(&tx).await;
+            tx.commit()?;
+
+            Ok(res)
+        }
+    }
+}
+
+impl<DB: AsyncDatabase> AsyncDatabase for Arc<DB> {
+    type TX = <DB as AsyncDatabase>::TX;
+    type TXMut = <DB as AsyncDatabase>::TXMut;
+
+    fn tx(&self) -> impl Future<Output = Result<Self::TX, DatabaseError>> + Send {
+        <DB as AsyncDatabase>::tx(self)
+    }
+
+    fn tx_mut(&self) -> impl Future<Output = Result<Self::TXMut, DatabaseError>> + Send {
+        <DB as AsyncDatabase>::tx_mut(self)
+    }
+}
+
+impl<DB: AsyncDatabase> AsyncDatabase for &DB {
+    type TX = <DB as AsyncDatabase>::TX;
+    type TXMut = <DB as AsyncDatabase>::TXMut;
+
+    fn tx(&self) -> impl Future<Output = Result<Self::TX, DatabaseError>> + Send {
+        <DB as AsyncDatabase>::tx(self)
+    }
+
+    fn tx_mut(&self) -> impl Future<Output = Result<Self::TXMut, DatabaseError>> + Send {
+        <DB as AsyncDatabase>::tx_mut(self)
+    }
+}
+
+/// Adapter that implements the synchronous [`Database`] trait by driving an [`AsyncDatabase`]
+/// to completion on a Tokio runtime handle.
+///
+/// This lets async-native backends plug into stages and other consumers that are written
+/// against the synchronous [`Database`] trait without any changes on their end.
+///
+/// Callers must not invoke this from within an async task already running on `handle`'s
+/// runtime: `Handle::block_on` panics ("Cannot block the current thread from within a runtime")
+/// if the current thread is already driving that runtime.
+#[derive(Debug)]
+pub struct BlockOn<DB> {
+    db: DB,
+    handle: tokio::runtime::Handle,
+}
+
+impl<DB> BlockOn<DB> {
+    /// Wraps `db` so it can be driven synchronously on the given runtime `handle`.
+    pub fn new(db: DB, handle: tokio::runtime::Handle) -> Self {
+        Self { db, handle }
+    }
+}
+
+impl<DB: AsyncDatabase> Sealed for BlockOn<DB> {}
+
+impl<DB: AsyncDatabase> Database for BlockOn<DB> {
+    type TX = DB::TX;
+    type TXMut = DB::TXMut;
+
+    fn tx(&self) -> Result<Self::TX, DatabaseError> {
+        self.handle.block_on(self.db.tx())
+    }
+
+    fn tx_mut(&self) -> Result<Self::TXMut, DatabaseError> {
+        self.handle.block_on(self.db.tx_mut())
+    }
+}
+
+/// Object-safe, hand-written view of a read-only transaction's non-generic surface.
+///
+/// `DbTx` itself is not object-safe: its `get`/`cursor_read`/... methods are generic over
+/// `Table`, and a trait with generic methods (without `where Self: Sized`) cannot be turned into
+/// a `dyn Trait`. All a type-erased caller can actually still do with a transaction it didn't
+/// open generically over a table is decide whether to keep or discard it, so that's all this
+/// exposes. `commit`/`abort` take `self: Box<Self>` rather than `self` so the methods themselves
+/// stay object-safe.
+pub trait DynTx: Debug {
+    /// Commits the transaction, as [`DbTx::commit`].
+    fn commit(self: Box<Self>) -> Result<bool, DatabaseError>;
+
+    /// Aborts the transaction without committing, as [`DbTx::abort`].
+    fn abort(self: Box<Self>);
+}
+
+impl<T: DbTx + Debug> DynTx for T {
+    fn commit(self: Box<Self>) -> Result<bool, DatabaseError> {
+        DbTx::commit(*self)
+    }
+
+    fn abort(self: Box<Self>) {
+        DbTx::abort(*self)
+    }
+}
+
+/// Object-safe, hand-written view of a read-write transaction's non-generic surface; see
+/// [`DynTx`] for why `DbTxMut`/`TableImporter` can't be boxed directly. Write transactions are
+/// finished the same way read-only ones are, so this only adds the marker that a type
+/// implementing it is also a `DbTxMut`.
+pub trait DynTxMut: DynTx {}
+
+impl<T: DbTxMut + DbTx + Debug> DynTxMut for T {}
+
+/// Object-safe facade over [`Database`] that erases the `TX`/`TXMut` associated types behind
+/// boxed [`DynTx`]/[`DynTxMut`] trait objects.
+///
+/// Every [`Database`] implementor gets this for free via the blanket impl below. This is
+/// deliberately scoped to transaction *lifecycle* only — opening a transaction against a
+/// heterogeneous, erased backend and deciding whether to keep or discard it — which is enough
+/// for e.g. an orchestrator that commits or aborts a batch of write transactions across several
+/// concrete `Database` backends uniformly without reading or writing their contents itself.
+///
+/// `DbTx`/`DbTxMut`'s `get`/`put`/cursor methods are generic over `Table` and therefore not
+/// object-safe (see [`DynTx`]), so this facade cannot read or write table data — code that needs
+/// to do that has to go through the statically-typed [`Database`] trait for a concrete backend.
+/// It does NOT make it possible to swap in a fake `Database` and exercise real reads/writes
+/// through a `dyn` handle; for that, keep using the generic [`Database`] trait in tests too.
+pub trait DynDatabase: Send + Sync {
+    /// Create a boxed read-only transaction.
+    fn tx(&self) -> Result<Box<dyn DynTx + Send + Sync>, DatabaseError>;
+
+    /// Create a boxed read-write transaction.
+    fn tx_mut(&self) -> Result<Box<dyn DynTxMut + Send + Sync>, DatabaseError>;
+}
+
+impl<DB: Database> DynDatabase for DB {
+    fn tx(&self) -> Result<Box<dyn DynTx + Send + Sync>, DatabaseError> {
+        Ok(Box::new(Database::tx(self)?))
+    }
+
+    fn tx_mut(&self) -> Result<Box<dyn DynTxMut + Send + Sync>, DatabaseError> {
+        Ok(Box::new(Database::tx_mut(self)?))
+    }
+}
+
+/// A type-erased, reference-counted handle to any [`Database`], for lifecycle-only use; see
+/// [`DynDatabase`] for exactly what that covers (and doesn't).
+///
+/// Cloning is cheap (it's an `Arc` clone, so clones share one backend), and different
+/// `DatabaseRef` values can wrap different backends under the one type, which is useful for
+/// holding heterogeneous databases whose transactions
+/// need to be committed or aborted uniformly, without reading or writing through the handle
+/// itself.
+#[derive(Clone)]
+pub struct DatabaseRef(Arc<dyn DynDatabase>);
+
+impl DatabaseRef {
+    /// Erases `db`'s concrete type behind a [`DynDatabase`] trait object.
+    pub fn new<DB: Database + 'static>(db: DB) -> Self {
+        Self(Arc::new(db))
+    }
+
+    /// Create a boxed read-only transaction.
+    pub fn tx(&self) -> Result<Box<dyn DynTx + Send + Sync>, DatabaseError> {
+        self.0.tx()
+    }
+
+    /// Create a boxed read-write transaction.
+    pub fn tx_mut(&self) -> Result<Box<dyn DynTxMut + Send + Sync>, DatabaseError> {
+        self.0.tx_mut()
+    }
+}
+
+impl Debug for DatabaseRef {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DatabaseRef").finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[derive(Debug)]
+    enum TestError {
+        Closure,
+    }
+
+    impl From<DatabaseError> for TestError {
+        fn from(_: DatabaseError) -> Self {
+            unreachable!("try_update tests leave fails_remaining at 0, so commit never fails")
+        }
+    }
+
+    #[derive(Debug)]
+    struct FakeTxMut {
+        commits: Arc<AtomicUsize>,
+        aborts: Arc<AtomicUsize>,
+        /// Number of remaining `commit()` calls that should fail with a transient error before
+        /// succeeding, so tests can drive [`Database::update_with_retry`] through retries.
+        fails_remaining: Arc<AtomicUsize>,
+    }
+
+    impl DbTx for FakeTxMut {
+        fn commit(self) -> Result<bool, DatabaseError> {
+            if self
+                .fails_remaining
+                .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| (n > 0).then(|| n - 1))
+                .is_ok()
+            {
+                return Err(DatabaseError::Other("transient commit failure".to_string()));
+            }
+
+            self.commits.fetch_add(1, Ordering::SeqCst);
+            Ok(true)
+        }
+
+        fn abort(self) {
+            self.aborts.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    impl DbTxMut for FakeTxMut {}
+    impl TableImporter for FakeTxMut {}
+
+    #[derive(Debug, Clone, Default)]
+    struct FakeDb {
+        commits: Arc<AtomicUsize>,
+        aborts: Arc<AtomicUsize>,
+        /// Shared with every [`FakeTxMut`] this opens, so the failure countdown persists across
+        /// the fresh transactions [`Database::update_with_retry`] opens on each attempt.
+        fails_remaining: Arc<AtomicUsize>,
+        tx_opens: Arc<AtomicUsize>,
+    }
+
+    impl Sealed for FakeDb {}
+
+    impl Database for FakeDb {
+        type TX = FakeTxMut;
+        type TXMut = FakeTxMut;
+
+        fn tx(&self) -> Result<Self::TX, DatabaseError> {
+            self.tx_mut()
+        }
+
+        fn tx_mut(&self) -> Result<Self::TXMut, DatabaseError> {
+            self.tx_opens.fetch_add(1, Ordering::SeqCst);
+            Ok(FakeTxMut {
+                commits: self.commits.clone(),
+                aborts: self.aborts.clone(),
+                fails_remaining: self.fails_remaining.clone(),
+            })
+        }
+    }
+
+    #[test]
+    fn try_update_rolls_back_on_closure_error() {
+        let db = FakeDb::default();
+
+        let res = db.try_update(|_tx| Err::<(), TestError>(TestError::Closure));
+
+        assert!(matches!(res, Err(TestError::Closure)));
+        assert_eq!(db.commits.load(Ordering::SeqCst), 0, "commit must not be called on error");
+        assert_eq!(db.aborts.load(Ordering::SeqCst), 0, "rollback is a drop, not an abort call");
+    }
+
+    #[test]
+    fn try_update_commits_on_closure_success() {
+        let db = FakeDb::default();
+
+        let res = db.try_update(|_tx| Ok::<_, TestError>(7));
+
+        assert_eq!(res.unwrap(), 7);
+        assert_eq!(db.commits.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn update_with_retry_succeeds_after_retrying_transient_failures() {
+        let db = FakeDb { fails_remaining: Arc::new(AtomicUsize::new(2)), ..FakeDb::default() };
+        let invocations = Arc::new(AtomicUsize::new(0));
+        let policy = RetryPolicy::new(3, Duration::from_secs(0)).retry_if(|_| true);
+
+        let invocations_clone = invocations.clone();
+        let res = db.update_with_retry(policy, move |_tx| {
+            invocations_clone.fetch_add(1, Ordering::SeqCst);
+            99
+        });
+
+        assert_eq!(res.unwrap(), 99);
+        assert_eq!(invocations.load(Ordering::SeqCst), 3, "2 failed attempts + 1 success");
+        assert_eq!(db.tx_opens.load(Ordering::SeqCst), 3, "each attempt opens a fresh tx");
+        assert_eq!(db.commits.load(Ordering::SeqCst), 1, "only the final commit succeeds");
+    }
+
+    #[test]
+    fn update_with_retry_gives_up_after_max_attempts() {
+        let db = FakeDb { fails_remaining: Arc::new(AtomicUsize::new(10)), ..FakeDb::default() };
+        let invocations = Arc::new(AtomicUsize::new(0));
+        let policy = RetryPolicy::new(3, Duration::from_secs(0)).retry_if(|_| true);
+
+        let invocations_clone = invocations.clone();
+        let res = db.update_with_retry(policy, move |_tx| {
+            invocations_clone.fetch_add(1, Ordering::SeqCst);
+        });
+
+        assert!(res.is_err());
+        assert_eq!(invocations.load(Ordering::SeqCst), 3, "stops at max_attempts");
+        assert_eq!(db.tx_opens.load(Ordering::SeqCst), 3);
+        assert_eq!(db.commits.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn update_with_retry_propagates_non_retryable_error_immediately() {
+        let db = FakeDb { fails_remaining: Arc::new(AtomicUsize::new(10)), ..FakeDb::default() };
+        let invocations = Arc::new(AtomicUsize::new(0));
+        let policy = RetryPolicy::new(3, Duration::from_secs(0)).retry_if(|_| false);
+
+        let invocations_clone = invocations.clone();
+        let res = db.update_with_retry(policy, move |_tx| {
+            invocations_clone.fetch_add(1, Ordering::SeqCst);
+        });
+
+        assert!(res.is_err());
+        assert_eq!(invocations.load(Ordering::SeqCst), 1, "no retry once is_retryable says no");
+        assert_eq!(db.tx_opens.load(Ordering::SeqCst), 1);
+    }
+}
+
+#[cfg(test)]
+mod retry_policy_tests {
+    use super::*;
+
+    fn is_retryable_example(_: &DatabaseError) -> bool {
+        true
+    }
+
+    #[test]
+    #[should_panic(expected = "at least one attempt")]
+    fn new_rejects_zero_attempts() {
+        RetryPolicy::new(0, Duration::from_secs(0));
+    }
+
+    #[test]
+    fn retry_if_stores_the_given_predicate() {
+        let policy = RetryPolicy::new(3, Duration::from_secs(0)).retry_if(is_retryable_example);
+        assert!(policy.is_retryable(&DatabaseError::Other("transient".to_string())));
+    }
+}